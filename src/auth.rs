@@ -0,0 +1,176 @@
+use async_trait::async_trait;
+use axum::extract::{FromRequestParts, State};
+use axum::http::request::Parts;
+use axum::http::HeaderMap;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::Arc;
+
+use crate::{ApiError, AppState};
+
+/// The operation a handler is about to perform against a document,
+/// used by `ApiAuth::check_permission` to decide whether a `Principal`
+/// is allowed to proceed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Read,
+    Write,
+    Delete,
+}
+
+/// An authenticated caller. `NoAuth` hands out `Principal::anonymous()`
+/// for every request; real backends should fill in `id` with something
+/// meaningful for logging/auditing.
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub id: String,
+}
+
+impl Principal {
+    pub fn anonymous() -> Self {
+        Principal {
+            id: "anonymous".to_string(),
+        }
+    }
+}
+
+/// Pluggable authentication/authorization backend. Implementations decide
+/// how to turn request headers into a `Principal`, and whether that
+/// `Principal` may perform a given `Operation` on a given document.
+#[async_trait]
+pub trait ApiAuth: Send + Sync {
+    async fn authenticate(&self, headers: &HeaderMap) -> Result<Principal, ApiError>;
+    fn check_permission(&self, principal: &Principal, doc: &str, op: Operation) -> bool;
+}
+
+/// Default backend preserving pollon's historical behavior: everyone is
+/// an anonymous principal with unrestricted access.
+pub struct NoAuth;
+
+#[async_trait]
+impl ApiAuth for NoAuth {
+    async fn authenticate(&self, _headers: &HeaderMap) -> Result<Principal, ApiError> {
+        Ok(Principal::anonymous())
+    }
+
+    fn check_permission(&self, _principal: &Principal, _doc: &str, _op: Operation) -> bool {
+        true
+    }
+}
+
+/// One token's grant: which document(s) it applies to (`*` meaning all
+/// documents) and which operations it permits.
+#[derive(Debug, Clone)]
+struct Scope {
+    doc: String,
+    ops: HashSet<Operation>,
+}
+
+impl Scope {
+    fn allows(&self, doc: &str, op: Operation) -> bool {
+        (self.doc == "*" || self.doc == doc) && self.ops.contains(&op)
+    }
+}
+
+/// Bearer-token backend: reads a token -> scopes map from a file at
+/// startup (path from the `AUTH_TOKENS_FILE` env var) and authenticates
+/// requests against the `Authorization: Bearer <token>` header.
+///
+/// File format is one grant per line:
+///
+///     <token> <document-or-*> <ops-comma-separated>
+///
+/// e.g. `sekret42 notes read,write`. Blank lines and lines starting with
+/// `#` are ignored.
+pub struct BearerTokenAuth {
+    tokens: HashMap<String, Vec<Scope>>,
+}
+
+impl BearerTokenAuth {
+    /// Build a backend from the file named by `AUTH_TOKENS_FILE`.
+    ///
+    /// Returns `Ok(None)` only when `AUTH_TOKENS_FILE` is unset, which
+    /// callers should treat as "auth intentionally disabled". If the
+    /// variable is set but the file can't be read, that's a
+    /// misconfiguration, not an opt-out, so it's returned as an error
+    /// rather than folded into the `None` case.
+    pub fn from_env() -> Result<Option<Self>, ApiError> {
+        let path = match env::var("AUTH_TOKENS_FILE") {
+            Ok(path) => path,
+            Err(env::VarError::NotPresent) => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        let contents = std::fs::read_to_string(path)?;
+        Ok(Some(Self::parse(&contents)))
+    }
+
+    fn parse(contents: &str) -> Self {
+        let mut tokens: HashMap<String, Vec<Scope>> = HashMap::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let (Some(token), Some(doc), Some(ops)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            let ops = ops
+                .split(',')
+                .filter_map(|op| match op {
+                    "read" => Some(Operation::Read),
+                    "write" => Some(Operation::Write),
+                    "delete" => Some(Operation::Delete),
+                    _ => None,
+                })
+                .collect();
+            tokens.entry(token.to_string()).or_default().push(Scope {
+                doc: doc.to_string(),
+                ops,
+            });
+        }
+        BearerTokenAuth { tokens }
+    }
+}
+
+#[async_trait]
+impl ApiAuth for BearerTokenAuth {
+    async fn authenticate(&self, headers: &HeaderMap) -> Result<Principal, ApiError> {
+        let token = headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(ApiError::Unauthorized)?;
+        if self.tokens.contains_key(token) {
+            Ok(Principal {
+                id: token.to_string(),
+            })
+        } else {
+            Err(ApiError::Unauthorized)
+        }
+    }
+
+    fn check_permission(&self, principal: &Principal, doc: &str, op: Operation) -> bool {
+        self.tokens
+            .get(&principal.id)
+            .map(|scopes| scopes.iter().any(|scope| scope.allows(doc, op)))
+            .unwrap_or(false)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Principal {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let State(state) = State::<AppState>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| ApiError::Unauthorized)?;
+        state.auth.authenticate(&parts.headers).await
+    }
+}
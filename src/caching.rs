@@ -0,0 +1,134 @@
+use crate::storage::NodeInfo;
+use axum::http::{HeaderMap, HeaderValue};
+use std::time::SystemTime;
+
+/// Compute a strong ETag for a node from storage-reported metadata. We
+/// don't hash the contents (that would mean reading the node twice);
+/// size + mtime is cheap and changes whenever the node is rewritten.
+pub fn etag_for_node_info(info: &NodeInfo) -> String {
+    let mtime_nanos = info
+        .modified
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("\"{}-{}\"", info.len, mtime_nanos)
+}
+
+/// `Last-Modified`/`If-Modified-Since` use HTTP-date format (RFC 7231),
+/// not a raw timestamp, so format/parse through `httpdate`.
+pub fn last_modified_for_node_info(info: &NodeInfo) -> Option<HeaderValue> {
+    HeaderValue::from_str(&httpdate::fmt_http_date(info.modified)).ok()
+}
+
+/// `true` if any of the comma-separated values in an `If-Match`/
+/// `If-None-Match` header match `etag` (or the header is the wildcard `*`).
+pub fn if_header_matches(headers: &HeaderMap, name: axum::http::HeaderName, etag: &str) -> bool {
+    let Some(value) = headers.get(name).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    value
+        .split(',')
+        .map(|v| v.trim())
+        .any(|v| v == "*" || v == etag)
+}
+
+/// `true` if `If-Modified-Since` is present and not older than `modified`.
+pub fn if_modified_since_is_current(headers: &HeaderMap, modified: SystemTime) -> bool {
+    // HTTP-dates only have 1-second resolution, so round-trip `modified`
+    // through the same format/parse before comparing; otherwise a file
+    // with sub-second mtime always looks newer than the truncated
+    // `since` it reported a moment ago, even when nothing has changed.
+    let Ok(modified) = httpdate::parse_http_date(&httpdate::fmt_http_date(modified)) else {
+        return false;
+    };
+    headers
+        .get(axum::http::header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok())
+        .map(|since| modified <= since)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn info(len: u64, modified: SystemTime) -> NodeInfo {
+        NodeInfo { len, modified }
+    }
+
+    fn headers_with(name: axum::http::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn etag_changes_with_len_and_mtime() {
+        let base = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let a = etag_for_node_info(&info(10, base));
+        let b = etag_for_node_info(&info(11, base));
+        let c = etag_for_node_info(&info(10, base + Duration::from_secs(1)));
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a, etag_for_node_info(&info(10, base)));
+    }
+
+    #[test]
+    fn if_header_matches_exact_value() {
+        let headers = headers_with(axum::http::header::IF_MATCH, "\"a\", \"b\"");
+        assert!(if_header_matches(&headers, axum::http::header::IF_MATCH, "\"b\""));
+        assert!(!if_header_matches(&headers, axum::http::header::IF_MATCH, "\"c\""));
+    }
+
+    #[test]
+    fn if_header_matches_wildcard() {
+        let headers = headers_with(axum::http::header::IF_MATCH, "*");
+        assert!(if_header_matches(&headers, axum::http::header::IF_MATCH, "\"anything\""));
+    }
+
+    #[test]
+    fn if_header_matches_absent_header_is_false() {
+        let headers = HeaderMap::new();
+        assert!(!if_header_matches(&headers, axum::http::header::IF_MATCH, "\"a\""));
+    }
+
+    #[test]
+    fn if_modified_since_is_current_when_not_newer() {
+        let modified = httpdate::parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        let headers = headers_with(
+            axum::http::header::IF_MODIFIED_SINCE,
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+        );
+        assert!(if_modified_since_is_current(&headers, modified));
+    }
+
+    #[test]
+    fn if_modified_since_is_false_when_modified_is_newer() {
+        let since = httpdate::parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        let modified = since + Duration::from_secs(60);
+        let headers = headers_with(
+            axum::http::header::IF_MODIFIED_SINCE,
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+        );
+        assert!(!if_modified_since_is_current(&headers, modified));
+    }
+
+    #[test]
+    fn if_modified_since_is_current_despite_sub_second_mtime() {
+        let since = httpdate::parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        let modified = since + Duration::from_millis(123);
+        let headers = headers_with(
+            axum::http::header::IF_MODIFIED_SINCE,
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+        );
+        assert!(if_modified_since_is_current(&headers, modified));
+    }
+
+    #[test]
+    fn if_modified_since_absent_is_false() {
+        let headers = HeaderMap::new();
+        assert!(!if_modified_since_is_current(&headers, SystemTime::now()));
+    }
+}
@@ -0,0 +1,75 @@
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path as UrlPath, State};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::auth::{Operation, Principal};
+use crate::{ApiError, AppState};
+
+/// Which mutation happened to a node.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeOp {
+    Replace,
+    Delete,
+    Append,
+}
+
+/// Broadcast on every successful `node_replace`/`node_delete`/
+/// `document_append`, so `GET /:document/_ws` subscribers can react
+/// without polling.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeEvent {
+    pub document: String,
+    pub node: String,
+    pub op: ChangeOp,
+}
+
+/// `GET /:document/_ws` - upgrade to a WebSocket and stream `ChangeEvent`s
+/// for `document` as JSON text frames until the client disconnects.
+/// The static `_ws` segment takes precedence over `/:document/:node`, so
+/// a node whose id is `_ws` is shadowed and unreachable through
+/// `node_get`/`node_replace`/`node_delete`; every other node id is
+/// unaffected.
+pub async fn subscribe(
+    UrlPath(document): UrlPath<String>,
+    principal: Principal,
+    State(state): State<AppState>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    if !state.auth.check_permission(&principal, &document, Operation::Read) {
+        return Err(ApiError::Forbidden);
+    }
+    Ok(ws
+        .on_upgrade(move |socket| forward_changes(socket, document, state.changes.subscribe()))
+        .into_response())
+}
+
+async fn forward_changes(
+    mut socket: WebSocket,
+    document: String,
+    mut changes: broadcast::Receiver<ChangeEvent>,
+) {
+    loop {
+        let event = match changes.recv().await {
+            Ok(event) => event,
+            // A slow client fell behind the broadcast buffer and missed
+            // events, or the sender's gone; either way don't resume with
+            // a silent gap, close the connection so the client notices
+            // and reconnects/refetches.
+            Err(broadcast::error::RecvError::Lagged(_) | broadcast::error::RecvError::Closed) => {
+                return
+            }
+        };
+        if event.document != document {
+            continue;
+        }
+        let Ok(json) = serde_json::to_string(&event) else {
+            continue;
+        };
+        if socket.send(Message::Text(json)).await.is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+/// Serializes the check-then-write sequence (`If-Match` precondition
+/// check followed by `write_node`/`delete_node`) for a single node, and
+/// separately the scan-then-write sequence (`next_node_id` followed by
+/// `write_node`) for a whole document, so concurrent requests racing
+/// against the same node or the same document's id allocation can't both
+/// pass the check and both write. Locks are created lazily; one that
+/// nothing holds or waits on is dropped on a later `lock`/`lock_document`
+/// call.
+#[derive(Default)]
+pub struct NodeLocks {
+    locks: StdMutex<HashMap<(String, String), Arc<Mutex<()>>>>,
+    doc_locks: StdMutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl NodeLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Acquire the lock for `(doc, node)`, blocking until any other
+    /// in-flight write to the same node has finished. Hold the returned
+    /// guard across the precondition check and the storage call it
+    /// guards.
+    pub async fn lock(&self, doc: &str, node: &str) -> OwnedMutexGuard<()> {
+        let key = (doc.to_string(), node.to_string());
+        let entry = {
+            let mut locks = self.locks.lock().unwrap();
+            // Nobody else is waiting on these, so they'll never be locked
+            // again under their current Arc; drop them rather than growing
+            // the map forever as new node ids come and go.
+            locks.retain(|k, v| k == &key || Arc::strong_count(v) > 1);
+            locks
+                .entry(key)
+                .or_insert_with(|| Arc::new(Mutex::new(())))
+                .clone()
+        };
+        entry.lock_owned().await
+    }
+
+    /// Acquire the document-level lock for `doc`, blocking until any
+    /// other in-flight append to the same document has finished. Hold
+    /// the returned guard across `next_node_id` and the `write_node`
+    /// call it guards, so two concurrent appends can't compute the same
+    /// id and one silently clobber the other's write.
+    pub async fn lock_document(&self, doc: &str) -> OwnedMutexGuard<()> {
+        let entry = {
+            let mut locks = self.doc_locks.lock().unwrap();
+            locks.retain(|k, v| k == doc || Arc::strong_count(v) > 1);
+            locks
+                .entry(doc.to_string())
+                .or_insert_with(|| Arc::new(Mutex::new(())))
+                .clone()
+        };
+        entry.lock_owned().await
+    }
+}
@@ -1,6 +1,6 @@
 use axum::{
     extract::{FromRequest, Path as UrlPath, Query, State},
-    http::StatusCode,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{Html, IntoResponse, Response},
     routing::get,
     Router,
@@ -10,11 +10,30 @@ use dotenv::dotenv;
 use rust_embed::RustEmbed;
 use serde::Deserialize;
 use serde::Serialize;
-use std::{
-    env, fs,
-    path::{Component as PathComponent, Path, PathBuf},
+use futures::{StreamExt, TryStreamExt};
+use std::{env, fs, sync::Arc};
+use tokio::sync::broadcast;
+
+mod auth;
+use auth::{ApiAuth, NoAuth, Operation, Principal};
+
+mod caching;
+use caching::{
+    etag_for_node_info, if_header_matches, if_modified_since_is_current, last_modified_for_node_info,
 };
-use tokio::io::AsyncWriteExt;
+
+mod storage;
+use storage::{FsStorage, SledStorage, Storage};
+
+mod live;
+use live::{ChangeEvent, ChangeOp};
+
+mod locks;
+use locks::NodeLocks;
+
+mod streaming;
+
+mod middleware;
 
 // Response Conversion:
 
@@ -31,17 +50,26 @@ where
     }
 }
 
+#[derive(Debug)]
 enum ApiError {
     Simple(String),
     Io(std::io::Error),
     Utf8(std::str::Utf8Error),
     FromUtf8(std::string::FromUtf8Error),
     Var(std::env::VarError),
+    Unauthorized,
+    Forbidden,
+    Timeout,
+    NotFound,
 }
 
 impl From<std::io::Error> for ApiError {
     fn from(error: std::io::Error) -> Self {
-        Self::Io(error)
+        if error.kind() == std::io::ErrorKind::NotFound {
+            Self::NotFound
+        } else {
+            Self::Io(error)
+        }
     }
 }
 impl From<std::str::Utf8Error> for ApiError {
@@ -60,6 +88,24 @@ impl From<std::env::VarError> for ApiError {
     }
 }
 
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::Simple(error) => write!(f, "{}", error),
+            ApiError::Io(error) => write!(f, "{}", error),
+            ApiError::Utf8(error) => write!(f, "{}", error),
+            ApiError::FromUtf8(error) => write!(f, "{}", error),
+            ApiError::Var(error) => write!(f, "{}", error),
+            ApiError::Unauthorized => write!(f, "unauthorized"),
+            ApiError::Forbidden => write!(f, "forbidden"),
+            ApiError::Timeout => write!(f, "request timed out"),
+            ApiError::NotFound => write!(f, "not found"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
 // Custom Error formating
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
@@ -73,6 +119,16 @@ impl IntoResponse for ApiError {
             ApiError::Utf8(error) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", error)),
             ApiError::FromUtf8(error) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", error)),
             ApiError::Var(error) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", error)),
+            ApiError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                "missing or invalid credentials".to_string(),
+            ),
+            ApiError::Forbidden => (
+                StatusCode::FORBIDDEN,
+                "principal lacks permission for this document".to_string(),
+            ),
+            ApiError::Timeout => (StatusCode::REQUEST_TIMEOUT, "request timed out".to_string()),
+            ApiError::NotFound => (StatusCode::NOT_FOUND, "not found".to_string()),
         };
         (status, ApiJson(ErrorResponse { message })).into_response()
     }
@@ -80,14 +136,34 @@ impl IntoResponse for ApiError {
 
 #[derive(Clone)]
 struct AppState {
-    library: PathBuf,
+    storage: Arc<dyn Storage>,
+    auth: Arc<dyn ApiAuth>,
+    changes: broadcast::Sender<ChangeEvent>,
+    node_locks: Arc<NodeLocks>,
 }
 
 impl AppState {
     fn new() -> Self {
         let library_raw = env::var("LIBRARY").unwrap_or(env::var("PWD").unwrap());
+        let library = fs::canonicalize(library_raw).unwrap_or(".".into());
+        let storage: Arc<dyn Storage> = match env::var("SLED_PATH") {
+            Ok(sled_path) => {
+                Arc::new(SledStorage::open(sled_path).expect("failed to open sled database"))
+            }
+            Err(_) => Arc::new(FsStorage::new(library)),
+        };
+        let auth: Arc<dyn ApiAuth> = match auth::BearerTokenAuth::from_env()
+            .expect("AUTH_TOKENS_FILE is set but failed to load")
+        {
+            Some(backend) => Arc::new(backend),
+            None => Arc::new(NoAuth),
+        };
+        let (changes, _) = broadcast::channel(1024);
         AppState {
-            library: fs::canonicalize(library_raw).unwrap_or(".".into()),
+            storage,
+            auth,
+            changes,
+            node_locks: Arc::new(NodeLocks::new()),
         }
     }
 }
@@ -100,18 +176,30 @@ struct ClientAssets;
 async fn main() {
     dotenv().ok();
     let state = AppState::new();
-    println!("Serving {:?}", state.library);
     let api = Router::new()
         .route("/:document", get(document).post(document_append))
+        .route("/:document/_ws", get(live::subscribe))
         .route(
             "/:document/:node",
             get(node_get).put(node_replace).delete(node_delete),
         )
         .with_state(state);
     let client_assets = ServeEmbed::<ClientAssets>::new();
-    let app = Router::new()
+    let mut app = Router::new()
         .nest("/", api)
-        .nest_service("/client", client_assets);
+        .nest_service("/client", client_assets)
+        .layer(
+            tower::ServiceBuilder::new()
+                .layer(axum::error_handling::HandleErrorLayer::new(
+                    middleware::handle_timeout_error,
+                ))
+                .layer(tower::timeout::TimeoutLayer::new(
+                    middleware::request_timeout(),
+                )),
+        );
+    if let Some(cors) = middleware::cors_layer_from_env() {
+        app = app.layer(cors);
+    }
     let listener = tokio::net::TcpListener::bind("127.0.0.1:8080")
         .await
         .unwrap();
@@ -119,28 +207,6 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
-/// Append to a path if new_component is
-/// a normal component, so no .. or . or ../../
-fn path_append_normal<'a>(
-    path: &'a mut PathBuf,
-    new_component: &str,
-) -> Result<&'a PathBuf, ApiError> {
-    // We're going to pass this path to an OS API,
-    // from a user input, so lets do some sanitization.
-    // TODO hopefully there is a better way to do this.
-    match Path::new(&new_component).components().next() {
-        Some(PathComponent::Normal(raw)) => {
-            path.push(raw);
-            Ok(path)
-        }
-        Some(com) => Err(ApiError::Simple(format!(
-            "`{}` contains invalid component `{:#?}`",
-            new_component, com
-        ))),
-        None => Err(ApiError::Simple("Unknown Error".to_string())),
-    }
-}
-
 #[derive(Deserialize)]
 struct NodeSet {
     nodes: String,
@@ -149,110 +215,164 @@ struct NodeSet {
 async fn document(
     UrlPath(doc_raw): UrlPath<String>,
     nodes: Option<Query<NodeSet>>,
+    principal: Principal,
     State(state): State<AppState>,
-) -> Result<Html<String>, ApiError> {
-    let mut rendered = String::new();
-    let mut paths = vec![];
-    let mut doc_path = state.library.clone();
-    let doc_path = path_append_normal(&mut doc_path, &doc_raw)?;
-    if let Some(nodes) = nodes {
-        let nodes = nodes.nodes.split(",");
-        for node in nodes {
-            let node = format!("{}.html", node);
-            let mut path = doc_path.clone();
-            let path = path_append_normal(&mut path, &node)?;
-            if let Some(path) = path.as_path().to_str() {
-                paths.push(path.to_string());
-            }
-        }
-    } else {
-        let mut dir = tokio::fs::read_dir(doc_path).await?;
-        while let Some(ent) = dir.next_entry().await? {
-            if let Some(path) = ent.path().as_path().to_str() {
-                paths.push(path.to_string());
-            }
-        }
-        paths.sort();
-    }
-    for path in paths {
-        // TODO use spawn blocking to reduce thread spam.
-        let file = tokio::fs::read(path).await?;
-        rendered.push_str(std::str::from_utf8(&file)?);
+) -> Result<Response, ApiError> {
+    if !state.auth.check_permission(&principal, &doc_raw, Operation::Read) {
+        return Err(ApiError::Forbidden);
     }
-    Ok(Html(rendered))
+    let ids: Vec<String> = if let Some(Query(nodes)) = nodes {
+        nodes.nodes.split(',').map(|node| node.to_string()).collect()
+    } else {
+        state.storage.list_nodes(&doc_raw).await?
+    };
+
+    let storage = state.storage.clone();
+    let doc = doc_raw.clone();
+    let node_bytes = Box::pin(
+        futures::stream::iter(ids)
+            .then(move |id| {
+                let storage = storage.clone();
+                let doc = doc.clone();
+                async move { storage.read_node_stream(&doc, &id).await }
+            })
+            .try_flatten(),
+    );
+    let node_bytes = streaming::with_deadline(node_bytes, middleware::request_timeout());
+    let body = axum::body::Body::from_stream(streaming::validate_utf8(Box::pin(node_bytes)));
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(body)
+        .unwrap())
 }
 
 async fn node_get(
     UrlPath((doc_raw, node_raw)): UrlPath<(String, String)>,
+    principal: Principal,
     State(state): State<AppState>,
-) -> Result<Html<String>, ApiError> {
-    let mut node_path = state.library.clone();
-    path_append_normal(&mut node_path, &doc_raw)?;
-    let node_file = format!("{}.html", node_raw);
-    path_append_normal(&mut node_path, &node_file)?;
-    let file = tokio::fs::read(node_path).await?;
-    Ok(Html(String::from_utf8(file)?))
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    if !state.auth.check_permission(&principal, &doc_raw, Operation::Read) {
+        return Err(ApiError::Forbidden);
+    }
+    let info = state.storage.node_info(&doc_raw, &node_raw).await?;
+    let etag = etag_for_node_info(&info);
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+    if let Some(last_modified) = last_modified_for_node_info(&info) {
+        response_headers.insert(header::LAST_MODIFIED, last_modified);
+    }
+
+    let not_modified = if headers.contains_key(header::IF_NONE_MATCH) {
+        if_header_matches(&headers, header::IF_NONE_MATCH, &etag)
+    } else {
+        if_modified_since_is_current(&headers, info.modified)
+    };
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    let bytes = state.storage.read_node(&doc_raw, &node_raw).await?;
+    Ok((response_headers, Html(String::from_utf8(bytes)?)).into_response())
+}
+
+/// If the request carries `If-Match`, return `true` if the precondition
+/// fails (the node's current ETag doesn't match any value in the
+/// header) and the caller should answer `412 Precondition Failed`
+/// instead of writing. A node that doesn't exist yet has no ETag to
+/// fail against, so `If-Match` is ignored and the write proceeds,
+/// preserving today's create-on-write behavior.
+///
+/// This check is stat-then-act: the caller must hold that node's
+/// `NodeLocks` guard for the duration of the check *and* the write/delete
+/// it guards, or two concurrent requests can both observe a passing
+/// ETag and both proceed.
+async fn if_match_precondition_failed(
+    headers: &HeaderMap,
+    storage: &dyn Storage,
+    doc: &str,
+    node: &str,
+) -> bool {
+    if !headers.contains_key(header::IF_MATCH) {
+        return false;
+    }
+    let Ok(info) = storage.node_info(doc, node).await else {
+        return false;
+    };
+    let etag = etag_for_node_info(&info);
+    !if_header_matches(headers, header::IF_MATCH, &etag)
 }
 
 async fn node_replace(
     UrlPath((doc_raw, node_raw)): UrlPath<(String, String)>,
+    principal: Principal,
     State(state): State<AppState>,
+    headers: HeaderMap,
     node_body: String,
 ) -> Result<StatusCode, ApiError> {
-    let mut node_path = state.library.clone();
-    path_append_normal(&mut node_path, &doc_raw)?;
-    let node_file = format!("{}.html", node_raw);
-    path_append_normal(&mut node_path, &node_file)?;
-    tokio::fs::write(node_path, node_body).await?;
+    if !state.auth.check_permission(&principal, &doc_raw, Operation::Write) {
+        return Err(ApiError::Forbidden);
+    }
+    let _guard = state.node_locks.lock(&doc_raw, &node_raw).await;
+    if if_match_precondition_failed(&headers, state.storage.as_ref(), &doc_raw, &node_raw).await {
+        return Ok(StatusCode::PRECONDITION_FAILED);
+    }
+    state
+        .storage
+        .write_node(&doc_raw, &node_raw, node_body.into_bytes())
+        .await?;
+    let _ = state.changes.send(ChangeEvent {
+        document: doc_raw,
+        node: node_raw,
+        op: ChangeOp::Replace,
+    });
     Ok(StatusCode::RESET_CONTENT)
 }
 
 async fn node_delete(
     UrlPath((doc_raw, node_raw)): UrlPath<(String, String)>,
+    principal: Principal,
     State(state): State<AppState>,
+    headers: HeaderMap,
 ) -> Result<StatusCode, ApiError> {
-    let mut node_path = state.library.clone();
-    path_append_normal(&mut node_path, &doc_raw)?;
-    let node_file = format!("{}.html", node_raw);
-    path_append_normal(&mut node_path, &node_file)?;
-    tokio::fs::remove_file(node_path).await?;
+    if !state.auth.check_permission(&principal, &doc_raw, Operation::Delete) {
+        return Err(ApiError::Forbidden);
+    }
+    let _guard = state.node_locks.lock(&doc_raw, &node_raw).await;
+    if if_match_precondition_failed(&headers, state.storage.as_ref(), &doc_raw, &node_raw).await {
+        return Ok(StatusCode::PRECONDITION_FAILED);
+    }
+    state.storage.delete_node(&doc_raw, &node_raw).await?;
+    let _ = state.changes.send(ChangeEvent {
+        document: doc_raw,
+        node: node_raw,
+        op: ChangeOp::Delete,
+    });
     Ok(StatusCode::RESET_CONTENT)
 }
 
 async fn document_append(
     UrlPath(doc_raw): UrlPath<String>,
+    principal: Principal,
     State(state): State<AppState>,
     node_body: String,
 ) -> Result<StatusCode, ApiError> {
-    let mut doc_path = state.library.clone();
-    let doc_path = path_append_normal(&mut doc_path, &doc_raw)?;
-    let mut dir = tokio::fs::read_dir(doc_path).await?;
-    let mut new_canidate: u32 = 0;
-    while let Some(ent) = dir.next_entry().await? {
-        let path = ent.file_name();
-        if !ent.file_type().await?.is_file() {
-            continue;
-        }
-        let Some(path) = path.to_str() else {
-            continue;
-        };
-        let path = path.strip_suffix(".html").unwrap_or(path);
-        let Ok(value) = path.parse::<u32>() else {
-            continue;
-        };
-        if value >= new_canidate {
-            new_canidate = value + 1;
-        }
+    if !state.auth.check_permission(&principal, &doc_raw, Operation::Write) {
+        return Err(ApiError::Forbidden);
     }
-    let node_raw = format!("{}.html", new_canidate);
-    let mut node_path = doc_path.clone();
-    path_append_normal(&mut node_path, &node_raw)?;
-    let mut file = tokio::fs::OpenOptions::new()
-        .write(true)
-        .create_new(true)
-        .open(node_path)
+    let _guard = state.node_locks.lock_document(&doc_raw).await;
+    let new_candidate = state.storage.next_node_id(&doc_raw).await?;
+    let node_raw = new_candidate.to_string();
+    state
+        .storage
+        .write_node(&doc_raw, &node_raw, node_body.into_bytes())
         .await?;
-    file.write_all(node_body.as_bytes()).await?;
-    file.flush().await?;
+    let _ = state.changes.send(ChangeEvent {
+        document: doc_raw,
+        node: node_raw,
+        op: ChangeOp::Append,
+    });
     Ok(StatusCode::OK)
 }
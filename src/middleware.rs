@@ -0,0 +1,60 @@
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE, IF_MATCH, IF_NONE_MATCH};
+use axum::http::Method;
+use axum::response::IntoResponse;
+use axum::BoxError;
+use std::env;
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::ApiError;
+
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 5;
+
+/// Duration after which a handler is aborted and answered `408`, read
+/// from `REQUEST_TIMEOUT` (seconds); this matters because
+/// `node_replace`/`document_append` write to disk and `document` can
+/// read many nodes.
+pub fn request_timeout() -> Duration {
+    let secs = env::var("REQUEST_TIMEOUT")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// `tower::timeout::TimeoutLayer` reports a timed-out request as a
+/// boxed `Elapsed` error; translate that (and anything else that
+/// somehow reaches here) into a proper `ApiError` response.
+pub async fn handle_timeout_error(error: BoxError) -> impl IntoResponse {
+    if error.is::<tower::timeout::error::Elapsed>() {
+        ApiError::Timeout.into_response()
+    } else {
+        ApiError::Simple(format!("unhandled middleware error: {}", error)).into_response()
+    }
+}
+
+/// Build a CORS layer from a comma-separated `CORS_ALLOWED_ORIGINS` env
+/// var, echoing back whichever single allowlisted origin matches the
+/// request's `Origin` (never `*`). Returns `None` when the var is unset
+/// so pollon stays same-origin-only by default.
+pub fn cors_layer_from_env() -> Option<CorsLayer> {
+    let allowed: Vec<String> = env::var("CORS_ALLOWED_ORIGINS")
+        .ok()?
+        .split(',')
+        .map(|origin| origin.trim().to_string())
+        .filter(|origin| !origin.is_empty())
+        .collect();
+    if allowed.is_empty() {
+        return None;
+    }
+    Some(
+        CorsLayer::new()
+            .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+            .allow_headers([AUTHORIZATION, IF_MATCH, IF_NONE_MATCH, CONTENT_TYPE])
+            .allow_origin(AllowOrigin::predicate(move |origin, _| {
+                allowed
+                    .iter()
+                    .any(|allowed| allowed.as_bytes() == origin.as_bytes())
+            })),
+    )
+}
@@ -0,0 +1,130 @@
+use super::{validate_component, NodeInfo, NodeStream, Storage};
+use crate::ApiError;
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::path::PathBuf;
+use tokio_util::io::ReaderStream;
+
+/// The original pollon storage layout: one directory per document under
+/// `library`, one `<node-id>.html` file per node.
+pub struct FsStorage {
+    library: PathBuf,
+}
+
+impl FsStorage {
+    pub fn new(library: PathBuf) -> Self {
+        FsStorage { library }
+    }
+
+    fn doc_path(&self, doc: &str) -> Result<PathBuf, ApiError> {
+        Ok(self.library.join(validate_component(doc)?))
+    }
+
+    fn node_path(&self, doc: &str, node: &str) -> Result<PathBuf, ApiError> {
+        let node = validate_component(node)?;
+        Ok(self.doc_path(doc)?.join(format!("{}.html", node)))
+    }
+}
+
+#[async_trait]
+impl Storage for FsStorage {
+    async fn read_node(&self, doc: &str, node: &str) -> Result<Vec<u8>, ApiError> {
+        Ok(tokio::fs::read(self.node_path(doc, node)?).await?)
+    }
+
+    async fn read_node_stream(&self, doc: &str, node: &str) -> Result<NodeStream, ApiError> {
+        let file = tokio::fs::File::open(self.node_path(doc, node)?).await?;
+        let stream = ReaderStream::new(file).map(|chunk| chunk.map_err(ApiError::from));
+        Ok(Box::pin(stream))
+    }
+
+    async fn write_node(&self, doc: &str, node: &str, bytes: Vec<u8>) -> Result<(), ApiError> {
+        tokio::fs::write(self.node_path(doc, node)?, bytes).await?;
+        Ok(())
+    }
+
+    async fn delete_node(&self, doc: &str, node: &str) -> Result<(), ApiError> {
+        tokio::fs::remove_file(self.node_path(doc, node)?).await?;
+        Ok(())
+    }
+
+    async fn node_info(&self, doc: &str, node: &str) -> Result<NodeInfo, ApiError> {
+        let metadata = tokio::fs::metadata(self.node_path(doc, node)?).await?;
+        Ok(NodeInfo {
+            len: metadata.len(),
+            modified: metadata.modified()?,
+        })
+    }
+
+    async fn list_nodes(&self, doc: &str) -> Result<Vec<String>, ApiError> {
+        let mut ids = vec![];
+        let mut dir = tokio::fs::read_dir(self.doc_path(doc)?).await?;
+        while let Some(ent) = dir.next_entry().await? {
+            if !ent.file_type().await?.is_file() {
+                continue;
+            }
+            if let Some(name) = ent.file_name().to_str() {
+                ids.push(name.strip_suffix(".html").unwrap_or(name).to_string());
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Unlike `list_nodes`, this is a write-path helper: appending the
+    /// first node to a document that has no directory yet must still
+    /// work, so this creates the directory if needed rather than
+    /// requiring it to already exist.
+    async fn next_node_id(&self, doc: &str) -> Result<u32, ApiError> {
+        let doc_path = self.doc_path(doc)?;
+        tokio::fs::create_dir_all(&doc_path).await?;
+        let mut next_candidate: u32 = 0;
+        let mut dir = tokio::fs::read_dir(&doc_path).await?;
+        while let Some(ent) = dir.next_entry().await? {
+            if !ent.file_type().await?.is_file() {
+                continue;
+            }
+            let Some(name) = ent.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            let id = name.strip_suffix(".html").unwrap_or(&name);
+            if let Ok(value) = id.parse::<u32>() {
+                if value >= next_candidate {
+                    next_candidate = value + 1;
+                }
+            }
+        }
+        Ok(next_candidate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn temp_storage() -> FsStorage {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let library = std::env::temp_dir().join(format!("pollon-fs-test-{}-{}", std::process::id(), n));
+        FsStorage::new(library)
+    }
+
+    #[tokio::test]
+    async fn next_node_id_on_a_never_written_document_is_zero() {
+        let storage = temp_storage();
+        assert_eq!(storage.next_node_id("newdoc").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn document_append_then_read_round_trips() {
+        let storage = temp_storage();
+        let id = storage.next_node_id("newdoc").await.unwrap();
+        storage
+            .write_node("newdoc", &id.to_string(), b"hello".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(storage.read_node("newdoc", "0").await.unwrap(), b"hello");
+        assert_eq!(storage.next_node_id("newdoc").await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::ApiError;
+use async_trait::async_trait;
+use axum::body::Bytes;
+use futures::Stream;
+use std::path::{Component as PathComponent, Path};
+use std::pin::Pin;
+use std::time::SystemTime;
+
+mod fs;
+mod sled;
+pub use fs::FsStorage;
+pub use sled::SledStorage;
+
+/// A node's bytes, read incrementally rather than buffered all at once.
+pub type NodeStream = Pin<Box<dyn Stream<Item = Result<Bytes, ApiError>> + Send>>;
+
+/// Metadata pollon needs for conditional-request support (ETags,
+/// `Last-Modified`) without assuming a particular storage engine's
+/// notion of "a file on disk".
+#[derive(Debug, Clone, Copy)]
+pub struct NodeInfo {
+    pub len: u64,
+    pub modified: SystemTime,
+}
+
+/// Backing store for documents and their nodes. `FsStorage` preserves
+/// pollon's historical directory-of-files layout; `SledStorage` keeps
+/// the same semantics on top of a single embedded key-value database,
+/// except that `SledStorage::write_node` creates a document that was
+/// never written, where `FsStorage::write_node` fails with `NotFound`.
+/// Both own path/key sanitization, so handlers never build a `PathBuf`
+/// (or sled key) from user input themselves.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn read_node(&self, doc: &str, node: &str) -> Result<Vec<u8>, ApiError>;
+    /// Like `read_node`, but yields the node's bytes as they become
+    /// available instead of buffering the whole thing in memory first.
+    async fn read_node_stream(&self, doc: &str, node: &str) -> Result<NodeStream, ApiError>;
+    async fn write_node(&self, doc: &str, node: &str, bytes: Vec<u8>) -> Result<(), ApiError>;
+    async fn delete_node(&self, doc: &str, node: &str) -> Result<(), ApiError>;
+    async fn node_info(&self, doc: &str, node: &str) -> Result<NodeInfo, ApiError>;
+    /// Sorted ids of every node currently stored under `doc`. Must fail
+    /// with `ApiError::NotFound` if `doc` has never been written to —
+    /// backends must not implicitly create a document on this read path.
+    async fn list_nodes(&self, doc: &str) -> Result<Vec<String>, ApiError>;
+    /// The id `document_append` should use for its next node: one past
+    /// the highest existing numeric node id in `doc`. Unlike
+    /// `list_nodes`, this is a write-path call: appending to a document
+    /// that has never been written must still succeed (as if it had zero
+    /// nodes), so backends create whatever backing structure they need
+    /// (a directory, a sled tree) here rather than requiring it to
+    /// already exist.
+    async fn next_node_id(&self, doc: &str) -> Result<u32, ApiError>;
+}
+
+/// Reject anything but a single, non-traversing path component, e.g. a
+/// document or node id arriving raw from a URL segment.
+pub(crate) fn validate_component(raw: &str) -> Result<&str, ApiError> {
+    let mut components = Path::new(raw).components();
+    match (components.next(), components.next()) {
+        (Some(PathComponent::Normal(_)), None) => Ok(raw),
+        (Some(com), _) => Err(ApiError::Simple(format!(
+            "`{}` contains invalid component `{:#?}`",
+            raw, com
+        ))),
+        (None, _) => Err(ApiError::Simple("Unknown Error".to_string())),
+    }
+}
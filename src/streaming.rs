@@ -0,0 +1,111 @@
+use async_stream::try_stream;
+use axum::body::Bytes;
+use futures::{Stream, StreamExt};
+use std::time::Duration;
+
+use crate::ApiError;
+
+/// Re-validate a byte stream as UTF-8 as it arrives, without assuming
+/// chunk boundaries line up with codepoint boundaries. Bytes that
+/// complete a valid prefix are yielded immediately; a trailing partial
+/// codepoint is held back and prepended to the next chunk. Genuinely
+/// invalid UTF-8 ends the stream with `ApiError::Utf8`.
+pub fn validate_utf8<S>(mut input: S) -> impl Stream<Item = Result<Bytes, ApiError>>
+where
+    S: Stream<Item = Result<Bytes, ApiError>> + Unpin,
+{
+    try_stream! {
+        let mut pending: Vec<u8> = Vec::new();
+        while let Some(chunk) = input.next().await {
+            pending.extend_from_slice(&chunk?);
+            match std::str::from_utf8(&pending) {
+                Ok(_) => {
+                    yield Bytes::from(std::mem::take(&mut pending));
+                }
+                Err(error) => {
+                    let valid_len = error.valid_up_to();
+                    if valid_len > 0 {
+                        let remainder = pending.split_off(valid_len);
+                        yield Bytes::from(std::mem::replace(&mut pending, remainder));
+                    }
+                    // `error_len` is `None` when `pending` simply ends
+                    // mid-codepoint (more bytes are on the way); `Some`
+                    // means the bytes are actually malformed.
+                    if error.error_len().is_some() {
+                        Err(ApiError::Utf8(std::str::from_utf8(&pending).unwrap_err()))?;
+                    }
+                }
+            }
+        }
+        if !pending.is_empty() {
+            std::str::from_utf8(&pending).map_err(ApiError::Utf8)?;
+            yield Bytes::from(pending);
+        }
+    }
+}
+
+/// Bound the time between successive chunks of `input` by `timeout`.
+///
+/// `tower::timeout::TimeoutLayer` only bounds the handler future, which
+/// for a streamed response resolves as soon as the body is constructed —
+/// not while it's later polled during body streaming. For `document`,
+/// which can stream many (or slow) nodes, that leaves no deadline on the
+/// actual reads; this fills that gap by timing out the stream itself.
+pub fn with_deadline<S>(mut input: S, timeout: Duration) -> impl Stream<Item = Result<Bytes, ApiError>>
+where
+    S: Stream<Item = Result<Bytes, ApiError>> + Unpin,
+{
+    try_stream! {
+        loop {
+            match tokio::time::timeout(timeout, input.next()).await {
+                Ok(Some(chunk)) => yield chunk?,
+                Ok(None) => break,
+                Err(_) => Err(ApiError::Timeout)?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn collect(chunks: Vec<&[u8]>) -> Result<Vec<u8>, ApiError> {
+        let input = futures::stream::iter(chunks.into_iter().map(|c| Ok(Bytes::copy_from_slice(c))));
+        let mut output = Vec::new();
+        let mut stream = Box::pin(validate_utf8(input));
+        while let Some(chunk) = stream.next().await {
+            output.extend_from_slice(&chunk?);
+        }
+        Ok(output)
+    }
+
+    #[tokio::test]
+    async fn passes_through_ascii_chunks() {
+        let result = collect(vec![b"hello ", b"world"]).await.unwrap();
+        assert_eq!(result, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn reassembles_a_codepoint_split_across_chunks() {
+        // "é" (U+00E9) is encoded as the two bytes 0xC3 0xA9; split them
+        // across separate chunks to exercise the held-back `pending` path.
+        let full = "caf\u{e9}".as_bytes();
+        let result = collect(vec![&full[..3], &full[3..4], &full[4..]])
+            .await
+            .unwrap();
+        assert_eq!(result, full);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_utf8() {
+        let result = collect(vec![&[0xff, 0xfe]]).await;
+        assert!(matches!(result, Err(ApiError::Utf8(_))));
+    }
+
+    #[tokio::test]
+    async fn empty_input_yields_nothing() {
+        let result = collect(vec![]).await.unwrap();
+        assert!(result.is_empty());
+    }
+}